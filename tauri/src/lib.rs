@@ -1,9 +1,13 @@
 mod config;
 mod socket_server;
 
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 use std::sync::Mutex;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tauri::Emitter;
 use tauri::Manager;
 use tauri::AppHandle;
 use tauri::menu::{Menu, MenuItem};
@@ -12,35 +16,91 @@ use tauri::tray::TrayIconBuilder;
 #[cfg(not(debug_assertions))]
 use std::collections::HashMap;
 #[cfg(not(debug_assertions))]
+use std::sync::atomic::Ordering;
+#[cfg(not(debug_assertions))]
+use socket_server::get_socket_path;
+#[cfg(not(debug_assertions))]
+use tauri_plugin_shell::process::CommandEvent;
+#[cfg(not(debug_assertions))]
 use tauri_plugin_shell::ShellExt;
 
 use config::{Settings, SETTINGS};
-use socket_server::run_socket_server;
+use socket_server::{generate_control_token, run_socket_server};
+
+/// Number of sidecar log lines kept in memory for the diagnostics panel.
+const MAX_LOG_LINES: usize = 500;
+
+/// Initial delay before restarting a crashed sidecar.
+#[cfg(not(debug_assertions))]
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Upper bound on the restart backoff.
+#[cfg(not(debug_assertions))]
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// How long the sidecar must stay healthy before the backoff resets.
+#[cfg(not(debug_assertions))]
+const HEALTHY_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// Connection state of the FastAPI sidecar, pushed to the frontend as
+/// `backend-status` events.
+#[derive(Clone, Serialize)]
+enum BackendStatus {
+    Starting,
+    Ready,
+    Crashed,
+    Restarting,
+}
 
 // Global state to hold the sidecar process handle
 struct SidecarState {
     #[allow(dead_code)]  // Only used in non-debug builds
     child: Mutex<Option<tauri_plugin_shell::process::CommandChild>>,
+    /// Bearer token the sidecar must present to the control socket.
+    control_token: String,
+    /// Ring buffer of the sidecar's most recent stdout/stderr lines.
+    logs: Mutex<VecDeque<String>>,
+    /// Set while the sidecar is being stopped deliberately, so the
+    /// supervisor doesn't treat that exit as a crash to restart from.
+    #[allow(dead_code)] // Only used in non-debug builds
+    is_shutting_down: Mutex<bool>,
+    /// Bumped every time `start_backend` spawns a fresh sidecar + supervisor.
+    /// A running `supervise_backend` task compares its captured generation
+    /// against this value before acting on a termination, so a stale
+    /// supervisor left over from a previous `restart_server` call gives up
+    /// instead of "restarting" a sidecar that's already been replaced.
+    #[allow(dead_code)] // Only used in non-debug builds
+    supervisor_generation: AtomicU64,
+    /// Claimed (via `compare_exchange`) for the duration of `start_backend`'s
+    /// read-check-spawn-store sequence, so two overlapping `start_backend`
+    /// calls can't both observe an empty `child` and both spawn a sidecar.
+    #[allow(dead_code)] // Only used in non-debug builds
+    is_starting: AtomicBool,
 }
 
 unsafe impl Send for SidecarState {}
 unsafe impl Sync for SidecarState {}
 
-/// Wait for the backend to be ready by polling the health endpoint.
-/// Returns Ok(()) when backend is ready, Err after timeout.
-fn wait_for_backend() -> Result<(), String> {
-    let backend_url = SETTINGS.backend_url();
+/// Poll the backend's health endpoint until it responds successfully (or the
+/// configured timeout elapses), emitting a `backend-ready` event to the
+/// webview either way so the UI isn't left guessing. Runs async so the main
+/// window can open immediately with a loading state instead of the app
+/// blocking (or panicking on timeout) during startup.
+async fn wait_for_backend_async(app: &tauri::AppHandle) -> Result<(), String> {
     let health_endpoint = SETTINGS.health_endpoint();
     let timeout = SETTINGS.health_check_timeout();
-    let interval = SETTINGS.health_check_interval();
 
-    log::info!("Waiting for backend at {}...", backend_url);
+    log::info!("Waiting for backend at {}...", health_endpoint);
+
+    let client = reqwest::Client::new();
     let start = Instant::now();
+    let mut interval = tokio::time::interval(SETTINGS.health_check_interval());
 
     while start.elapsed() < timeout {
-        match ureq::get(&health_endpoint).call() {
-            Ok(response) if response.status() == 200 => {
+        interval.tick().await;
+
+        match client.get(&health_endpoint).send().await {
+            Ok(response) if response.status().is_success() => {
                 log::info!("Backend is ready (took {:?})", start.elapsed());
+                let _ = app.emit("backend-ready", true);
                 return Ok(());
             }
             Ok(response) => {
@@ -50,63 +110,257 @@ fn wait_for_backend() -> Result<(), String> {
                 log::debug!("Health check failed: {}", e);
             }
         }
-        thread::sleep(interval);
     }
 
-    Err(format!(
+    let message = format!(
         "Backend not ready after {:?}. Is it running? Try: make fastapi",
         timeout
-    ))
+    );
+    log::warn!("{}", message);
+    let _ = app.emit("backend-ready", false);
+    Err(message)
+}
+
+/// Spawn the FastAPI sidecar once, storing its child handle in
+/// `SidecarState` and returning the event receiver so the caller can watch
+/// it for crashes.
+#[cfg(not(debug_assertions))]
+fn spawn_sidecar_process(
+    app: &tauri::AppHandle,
+) -> Result<tokio::sync::mpsc::Receiver<CommandEvent>, String> {
+    // Get app data directory
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+
+    // Ensure directory exists
+    std::fs::create_dir_all(&data_dir)
+        .map_err(|e| format!("Failed to create data dir: {}", e))?;
+
+    log::info!("Starting FastAPI sidecar with DATA_DIR={:?}", data_dir);
+
+    // Set environment variables for the sidecar
+    let state = app.state::<SidecarState>();
+
+    let mut env: HashMap<String, String> = HashMap::new();
+    env.insert("DATA_DIR".into(), data_dir.to_string_lossy().to_string());
+    env.insert("HOST".into(), SETTINGS.host.clone());
+    env.insert("PORT".into(), SETTINGS.port.to_string());
+    env.insert("CONTROL_TOKEN".into(), state.control_token.clone());
+    env.insert(
+        "CONTROL_SOCKET_PATH".into(),
+        get_socket_path().to_string_lossy().to_string(),
+    );
+
+    // Spawn the sidecar
+    let (rx, child) = app
+        .shell()
+        .sidecar("fastapi-server")
+        .map_err(|e| format!("Failed to create sidecar command: {}", e))?
+        .envs(env)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
+
+    // Store the child handle for cleanup
+    *state.child.lock().unwrap() = Some(child);
+
+    log::info!("FastAPI sidecar spawned");
+
+    Ok(rx)
+}
+
+/// Watch the sidecar for an unexpected exit and restart it with capped
+/// exponential backoff, resetting the delay once it has stayed healthy for
+/// a while. Emits `backend-status` events so the UI can show connection
+/// state, and leaves deliberate exits (`stop_backend`) alone.
+///
+/// `generation` is the value of `SidecarState::supervisor_generation` at the
+/// moment this task's sidecar was spawned. If `start_backend` spawns a
+/// replacement (e.g. via `restart_server`) while this task is still
+/// draining a dead child's event stream, the generation check below makes
+/// this stale instance back off instead of "restarting" a process that's
+/// already been superseded.
+#[cfg(not(debug_assertions))]
+async fn supervise_backend(
+    app: tauri::AppHandle,
+    mut rx: tokio::sync::mpsc::Receiver<CommandEvent>,
+    generation: u64,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        if app.state::<SidecarState>().supervisor_generation.load(Ordering::SeqCst) != generation {
+            log::debug!("Superseded by a newer sidecar supervisor, exiting");
+            return;
+        }
+
+        let mut healthy_since = wait_for_backend_async(&app).await.ok().map(|_| Instant::now());
+
+        if healthy_since.is_some() {
+            let _ = app.emit("backend-status", BackendStatus::Ready);
+        }
+
+        loop {
+            let reset_after = healthy_since.map(|since| HEALTHY_RESET_AFTER.saturating_sub(since.elapsed()));
+
+            tokio::select! {
+                event = rx.recv() => {
+                    match event {
+                        Some(CommandEvent::Stdout(line)) => {
+                            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                            log::info!(target: "fastapi", "{}", line);
+                            push_log_line(&app, line);
+                        }
+                        Some(CommandEvent::Stderr(line)) => {
+                            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                            log::warn!(target: "fastapi", "{}", line);
+                            push_log_line(&app, line);
+                        }
+                        Some(CommandEvent::Terminated(payload)) => {
+                            log::warn!(
+                                target: "fastapi",
+                                "FastAPI sidecar terminated (code={:?}, signal={:?})",
+                                payload.code,
+                                payload.signal
+                            );
+                            // Drop the now-dead handle immediately so
+                            // `start_backend`'s liveness check doesn't treat
+                            // a crashed sidecar as still running while we're
+                            // backing off before the next respawn.
+                            app.state::<SidecarState>().child.lock().unwrap().take();
+                            break;
+                        }
+                        None => break,
+                        _ => {}
+                    }
+                }
+                // `reset_after` only goes Some once the sidecar has reported
+                // healthy; once it hits zero here, clear `healthy_since` so
+                // this arm disarms again instead of firing on every poll.
+                _ = tokio::time::sleep(reset_after.unwrap_or(Duration::MAX)), if reset_after.is_some() => {
+                    log::debug!("FastAPI sidecar healthy for {:?}, resetting restart backoff", HEALTHY_RESET_AFTER);
+                    backoff = INITIAL_BACKOFF;
+                    healthy_since = None;
+                }
+            }
+        }
+
+        if app.state::<SidecarState>().supervisor_generation.load(Ordering::SeqCst) != generation {
+            log::debug!("Superseded by a newer sidecar supervisor, exiting");
+            return;
+        }
+
+        if *app.state::<SidecarState>().is_shutting_down.lock().unwrap() {
+            log::info!("FastAPI sidecar stopped deliberately, supervisor exiting");
+            return;
+        }
+
+        let _ = app.emit("backend-status", BackendStatus::Crashed);
+        let _ = app.emit("backend-status", BackendStatus::Restarting);
+        log::warn!("Restarting FastAPI sidecar in {:?}", backoff);
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+
+        if app.state::<SidecarState>().supervisor_generation.load(Ordering::SeqCst) != generation {
+            log::debug!("Superseded by a newer sidecar supervisor, exiting");
+            return;
+        }
+
+        if *app.state::<SidecarState>().is_shutting_down.lock().unwrap() {
+            log::info!("FastAPI sidecar stopped deliberately during backoff, supervisor exiting");
+            return;
+        }
+
+        rx = match spawn_sidecar_process(&app) {
+            Ok(rx) => rx,
+            Err(e) => {
+                log::error!("Failed to restart FastAPI sidecar: {}", e);
+                continue;
+            }
+        };
+
+        let _ = app.emit("backend-status", BackendStatus::Starting);
+    }
 }
 
 /// Start the FastAPI backend.
 /// - In development: assumes backend is running separately (uvicorn --reload)
-/// - In production: spawns the bundled sidecar binary
+/// - In production: spawns the bundled sidecar binary and supervises it
+///
+/// A no-op if the sidecar is already running, so callers (a manual "start"
+/// button, `restart_server`, app setup) can call this freely without
+/// leaking a duplicate process.
+///
+/// Returns as soon as the sidecar is launched; readiness is reported
+/// asynchronously via the `backend-ready` event so callers (including app
+/// setup) never block on it.
 #[allow(unused_variables)]
 fn start_backend(app: &tauri::AppHandle) -> Result<(), String> {
     if Settings::is_dev_mode() {
         log::info!("Dev mode: expecting FastAPI backend at {}", SETTINGS.backend_url());
         log::info!("Run: make fastapi");
+
+        // No supervisor in dev mode to poll health and emit `backend-ready`,
+        // so do it here instead.
+        let app_handle = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = wait_for_backend_async(&app_handle).await;
+        });
     } else {
         #[cfg(not(debug_assertions))]
         {
-            // Get app data directory
-            let data_dir = app
-                .path()
-                .app_data_dir()
-                .map_err(|e| format!("Failed to get app data dir: {}", e))?;
-
-            // Ensure directory exists
-            std::fs::create_dir_all(&data_dir)
-                .map_err(|e| format!("Failed to create data dir: {}", e))?;
-
-            log::info!("Starting FastAPI sidecar with DATA_DIR={:?}", data_dir);
-
-            // Set environment variables for the sidecar
-            let mut env: HashMap<String, String> = HashMap::new();
-            env.insert("DATA_DIR".into(), data_dir.to_string_lossy().to_string());
-            env.insert("HOST".into(), SETTINGS.host.clone());
-            env.insert("PORT".into(), SETTINGS.port.to_string());
-
-            // Spawn the sidecar
-            let (_rx, child) = app
-                .shell()
-                .sidecar("fastapi-server")
-                .map_err(|e| format!("Failed to create sidecar command: {}", e))?
-                .envs(env)
-                .spawn()
-                .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
-
-            // Store the child handle for cleanup
             let state = app.state::<SidecarState>();
-            *state.child.lock().unwrap() = Some(child);
 
-            log::info!("FastAPI sidecar spawned");
+            if state
+                .is_starting
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                log::info!("start_backend: already starting, no-op");
+                return Ok(());
+            }
+
+            let result = (|| {
+                if state.child.lock().unwrap().is_some() {
+                    log::info!("start_backend: sidecar already running, no-op");
+                    return Ok(None);
+                }
+
+                *state.is_shutting_down.lock().unwrap() = false;
+                let generation = state.supervisor_generation.fetch_add(1, Ordering::SeqCst) + 1;
+                let rx = spawn_sidecar_process(app)?;
+                Ok(Some((rx, generation)))
+            })();
+
+            state.is_starting.store(false, Ordering::SeqCst);
+
+            if let Some((rx, generation)) = result? {
+                // supervise_backend polls health and emits `backend-ready`
+                // itself; spawning a second poller here would just race it.
+                tauri::async_runtime::spawn(supervise_backend(app.clone(), rx, generation));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Append a sidecar log line to the ring buffer and emit it to the webview.
+#[cfg(not(debug_assertions))]
+fn push_log_line(app: &tauri::AppHandle, line: String) {
+    let state = app.state::<SidecarState>();
+    {
+        let mut logs = state.logs.lock().unwrap();
+        logs.push_back(line.clone());
+        if logs.len() > MAX_LOG_LINES {
+            logs.pop_front();
         }
     }
 
-    // Wait for backend to be ready (both dev and prod)
-    wait_for_backend()
+    if let Err(e) = app.emit("backend-log", line) {
+        log::warn!("Failed to emit backend-log event: {}", e);
+    }
 }
 
 /// Stop the FastAPI sidecar gracefully.
@@ -120,9 +374,15 @@ fn stop_backend(app: &tauri::AppHandle) {
     #[cfg(not(debug_assertions))]
     {
         let state = app.state::<SidecarState>();
-        let mut child_guard = state.child.lock().unwrap();
+        *state.is_shutting_down.lock().unwrap() = true;
 
-        if let Some(child) = child_guard.take() {
+        // Taken and the lock dropped immediately: the graceful-shutdown
+        // wait below can take up to 2s, and start_backend's idempotency
+        // check takes this same lock, so holding it here would stall any
+        // concurrent start_server call for that long.
+        let child = state.child.lock().unwrap().take();
+
+        if let Some(child) = child {
             log::info!("Stopping FastAPI sidecar...");
 
             let pid = child.pid();
@@ -184,6 +444,39 @@ fn stop_backend(app: &tauri::AppHandle) {
     }
 }
 
+/// Start the FastAPI backend. No-op (from the frontend's perspective) if
+/// it's already running; watch for `backend-ready`/`backend-status` events
+/// to track when it actually comes up.
+#[tauri::command]
+async fn start_server(app: tauri::AppHandle) -> Result<String, String> {
+    start_backend(&app)?;
+    Ok("Backend starting".to_string())
+}
+
+/// Stop the FastAPI backend.
+///
+/// `stop_backend` blocks its caller's thread for up to 2s waiting out a
+/// graceful SIGTERM, so it's run via `spawn_blocking` rather than directly
+/// on the async command's tokio worker.
+#[tauri::command]
+async fn stop_server(app: tauri::AppHandle) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || stop_backend(&app))
+        .await
+        .map_err(|e| format!("Stop task panicked: {}", e))?;
+    Ok("Backend stopped".to_string())
+}
+
+/// Restart the FastAPI backend, e.g. from a manual "restart" button in the UI.
+#[tauri::command]
+async fn restart_server(app: tauri::AppHandle) -> Result<String, String> {
+    let stop_handle = app.clone();
+    tokio::task::spawn_blocking(move || stop_backend(&stop_handle))
+        .await
+        .map_err(|e| format!("Stop task panicked: {}", e))?;
+    start_backend(&app)?;
+    Ok("Backend restarting".to_string())
+}
+
 /// Get the app data directory path
 #[tauri::command]
 fn get_data_dir(app: tauri::AppHandle) -> Result<String, String> {
@@ -199,6 +492,26 @@ fn is_dev_mode() -> bool {
     Settings::is_dev_mode()
 }
 
+/// Return the most recent FastAPI sidecar log lines for a diagnostics panel.
+#[tauri::command]
+fn get_backend_logs(app: tauri::AppHandle) -> Vec<String> {
+    let state = app.state::<SidecarState>();
+    state.logs.lock().unwrap().iter().cloned().collect()
+}
+
+/// PID of the currently running sidecar, if any. Used by the control socket
+/// on Windows to verify a connecting named-pipe client actually is the
+/// sidecar process it spawned, rather than trusting its claimed image path.
+#[cfg(windows)]
+pub(crate) fn sidecar_pid(app: &tauri::AppHandle) -> Option<u32> {
+    app.state::<SidecarState>()
+        .child
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|child| child.pid())
+}
+
 /// Toggle window maximize/restore
 #[tauri::command]
 fn toggle_window_maximize(app: tauri::AppHandle) -> Result<(), String> {
@@ -243,12 +556,18 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .manage(SidecarState {
             child: Mutex::new(None),
+            control_token: generate_control_token(),
+            logs: Mutex::new(VecDeque::with_capacity(MAX_LOG_LINES)),
+            is_shutting_down: Mutex::new(false),
+            supervisor_generation: AtomicU64::new(0),
+            is_starting: AtomicBool::new(false),
         })
         .setup(|app| {
-            // Start backend on app setup
+            // Kick off the backend without blocking so the window can open
+            // immediately; the frontend shows a loading state until it sees
+            // a `backend-ready` event.
             if let Err(e) = start_backend(app.handle()) {
                 log::error!("Failed to start backend: {}", e);
-                panic!("Failed to start backend: {}", e);
             }
 
             // Create system tray
@@ -256,15 +575,16 @@ pub fn run() {
                 log::error!("Failed to create system tray: {}", e);
             }
 
-            // Start Unix socket server for FastAPI communication
+            // Start the control socket server for FastAPI communication
             let app_handle = app.handle().clone();
+            let control_token = app.state::<SidecarState>().control_token.clone();
 
             thread::spawn(move || {
                 let rt = tokio::runtime::Runtime::new()
                     .expect("Failed to create tokio runtime");
 
                 rt.block_on(async {
-                    if let Err(e) = run_socket_server(app_handle).await {
+                    if let Err(e) = run_socket_server(app_handle, control_token).await {
                         log::error!("Socket server error: {}", e);
                     }
                 });
@@ -299,7 +619,15 @@ pub fn run() {
                 stop_backend(app.app_handle());
             }
         })
-        .invoke_handler(tauri::generate_handler![get_data_dir, is_dev_mode, toggle_window_maximize])
+        .invoke_handler(tauri::generate_handler![
+            get_data_dir,
+            is_dev_mode,
+            toggle_window_maximize,
+            get_backend_logs,
+            start_server,
+            stop_server,
+            restart_server
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -1,20 +1,51 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, Sse},
+        Response,
+    },
     routing::{get, post},
     Json, Router,
 };
+use futures::stream::{Stream, StreamExt};
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Manager};
-use tokio::net::UnixListener;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::signal;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct WindowStateRequest {
-    pub action: String,
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+
+/// A command sent over `/window`. Tagged on `action` so the body stays a
+/// flat, human-writable JSON object, e.g. `{"action": "SetTitle", "title": "..."}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action")]
+pub enum WindowCommand {
+    Minimize,
+    Maximize,
+    Unmaximize,
+    Toggle,
+    SetFullscreen { enabled: bool },
+    Show,
+    Hide,
+    SetFocus,
+    SetTitle { title: String },
+    /// Push an arbitrary event straight into the frontend, so the FastAPI
+    /// backend can talk to the webview without its own IPC channel.
+    Emit { event: String, payload: serde_json::Value },
 }
 
 #[derive(Debug, Serialize)]
@@ -23,85 +54,186 @@ pub struct SocketResponse {
     pub message: String,
 }
 
+/// A Tauri `WindowEvent`, flattened for forwarding over `/events`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowEventMessage {
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub app_handle: Arc<Mutex<Option<AppHandle>>>,
+    pub token: Arc<String>,
+    pub window_events: broadcast::Sender<WindowEventMessage>,
+}
+
+/// Generate a random per-run bearer token used to authenticate callers of
+/// the control socket. Handed to the sidecar as an env var and checked
+/// against the `Authorization` header on every request to `/window`.
+pub fn generate_control_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    // In dev mode FastAPI is a manually-started `make fastapi` process with
+    // no sidecar env to hand it `CONTROL_TOKEN`, so there's no token for it
+    // to present. Skip auth rather than permanently 401 every dev request.
+    if crate::config::Settings::is_dev_mode() {
+        return Ok(next.run(request).await);
+    }
+
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if token == state.token.as_str() => Ok(next.run(request).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
 }
 
 async fn health() -> &'static str {
     "OK"
 }
 
-async fn toggle_window_state(
+fn window_command_failed(e: tauri::Error) -> StatusCode {
+    log::error!("Window command failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+async fn handle_window_command(
     State(state): State<AppState>,
-    Json(req): Json<WindowStateRequest>,
+    Json(cmd): Json<WindowCommand>,
 ) -> Result<Json<SocketResponse>, StatusCode> {
     let handle_guard = state.app_handle.lock().unwrap();
-    if let Some(app_handle) = handle_guard.as_ref() {
-        if let Some(window) = app_handle.get_webview_window("main") {
-            match req.action.as_str() {
-                "toggle" => {
-                    let is_maximized = window.is_maximized().unwrap_or(false);
-                    if is_maximized {
-                        window.unmaximize().map_err(|e| {
-                            log::error!("Failed to unmaximize window: {}", e);
-                            StatusCode::INTERNAL_SERVER_ERROR
-                        })?;
-                        return Ok(Json(SocketResponse {
-                            success: true,
-                            message: "Window restored".to_string(),
-                        }));
-                    } else {
-                        window.maximize().map_err(|e| {
-                            log::error!("Failed to maximize window: {}", e);
-                            StatusCode::INTERNAL_SERVER_ERROR
-                        })?;
-                        return Ok(Json(SocketResponse {
-                            success: true,
-                            message: "Window maximized".to_string(),
-                        }));
-                    }
-                }
-                "maximize" => {
-                    window.maximize().map_err(|e| {
-                        log::error!("Failed to maximize window: {}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?;
-                    return Ok(Json(SocketResponse {
-                        success: true,
-                        message: "Window maximized".to_string(),
-                    }));
-                }
-                "restore" | "unmaximize" => {
-                    window.unmaximize().map_err(|e| {
-                        log::error!("Failed to unmaximize window: {}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?;
-                    return Ok(Json(SocketResponse {
-                        success: true,
-                        message: "Window restored".to_string(),
-                    }));
-                }
-                _ => {
-                    return Err(StatusCode::BAD_REQUEST);
-                }
+    let app_handle = handle_guard.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+
+    // `Emit` talks to the app handle directly and doesn't need a window.
+    if let WindowCommand::Emit { event, payload } = cmd {
+        app_handle.emit(&event, payload).map_err(|e| {
+            log::error!("Failed to emit {}: {}", event, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        return Ok(Json(SocketResponse {
+            success: true,
+            message: format!("Emitted {}", event),
+        }));
+    }
+
+    let window = app_handle.get_webview_window("main").ok_or_else(|| {
+        log::error!("Main window not found");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let message = match cmd {
+        WindowCommand::Minimize => {
+            window.minimize().map_err(window_command_failed)?;
+            "Window minimized"
+        }
+        WindowCommand::Maximize => {
+            window.maximize().map_err(window_command_failed)?;
+            "Window maximized"
+        }
+        WindowCommand::Unmaximize => {
+            window.unmaximize().map_err(window_command_failed)?;
+            "Window restored"
+        }
+        WindowCommand::Toggle => {
+            if window.is_maximized().unwrap_or(false) {
+                window.unmaximize().map_err(window_command_failed)?;
+                "Window restored"
+            } else {
+                window.maximize().map_err(window_command_failed)?;
+                "Window maximized"
             }
-        } else {
-            log::error!("Main window not found");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
-    } else {
-        Err(StatusCode::SERVICE_UNAVAILABLE)
+        WindowCommand::SetFullscreen { enabled } => {
+            window.set_fullscreen(enabled).map_err(window_command_failed)?;
+            "Window fullscreen updated"
+        }
+        WindowCommand::Show => {
+            window.show().map_err(window_command_failed)?;
+            "Window shown"
+        }
+        WindowCommand::Hide => {
+            window.hide().map_err(window_command_failed)?;
+            "Window hidden"
+        }
+        WindowCommand::SetFocus => {
+            window.set_focus().map_err(window_command_failed)?;
+            "Window focused"
+        }
+        WindowCommand::SetTitle { title } => {
+            window.set_title(&title).map_err(window_command_failed)?;
+            "Window title updated"
+        }
+        WindowCommand::Emit { .. } => unreachable!("Emit is handled above"),
+    };
+
+    Ok(Json(SocketResponse {
+        success: true,
+        message: message.to_string(),
+    }))
+}
+
+/// Stream Tauri `WindowEvent`s (resize/move/close) to the FastAPI backend
+/// as server-sent events, so it can react to the window without polling.
+async fn stream_window_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.window_events.subscribe()).filter_map(|message| async move {
+        let message = message.ok()?;
+        let data = serde_json::to_string(&message).ok()?;
+        Some(Ok(Event::default().event(message.event).data(data)))
+    });
+
+    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+}
+
+/// Translate a Tauri window event into the flat shape pushed over `/events`.
+fn describe_window_event(event: &tauri::WindowEvent) -> Option<WindowEventMessage> {
+    match event {
+        tauri::WindowEvent::Resized(size) => Some(WindowEventMessage {
+            event: "resize".to_string(),
+            payload: serde_json::json!({ "width": size.width, "height": size.height }),
+        }),
+        tauri::WindowEvent::Moved(position) => Some(WindowEventMessage {
+            event: "move".to_string(),
+            payload: serde_json::json!({ "x": position.x, "y": position.y }),
+        }),
+        tauri::WindowEvent::CloseRequested { .. } => Some(WindowEventMessage {
+            event: "close".to_string(),
+            payload: serde_json::Value::Null,
+        }),
+        _ => None,
     }
 }
 
 fn create_router(state: AppState) -> Router {
+    let protected = Router::new()
+        .route("/window", post(handle_window_command))
+        .route("/events", get(stream_window_events))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+
     Router::new()
         .route("/health", get(health))
-        .route("/window", post(toggle_window_state))
+        .merge(protected)
         .with_state(state)
 }
 
+/// Path (Unix) or named-pipe address (Windows) of the control channel.
+#[cfg(unix)]
 pub fn get_socket_path() -> PathBuf {
     let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
         .or_else(|_| std::env::var("TMP"))
@@ -111,30 +243,166 @@ pub fn get_socket_path() -> PathBuf {
     PathBuf::from(runtime_dir).join("tauri-fastapi.sock")
 }
 
-pub async fn run_socket_server(app_handle: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    let socket_path = get_socket_path();
+/// Path (Unix) or named-pipe address (Windows) of the control channel.
+#[cfg(windows)]
+pub fn get_socket_path() -> PathBuf {
+    PathBuf::from(r"\\.\pipe\tauri-fastapi")
+}
+
+/// Drive a single accepted connection through the axum router using the
+/// low-level hyper server, so the same handler works for both Unix sockets
+/// and Windows named pipes (neither of which axum::serve's Listener trait
+/// covers for named pipes).
+async fn serve_connection<S>(io: S, router: Router)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let io = TokioIo::new(io);
+    let service = TowerToHyperService::new(router);
 
+    if let Err(e) = hyper::server::conn::http1::Builder::new()
+        .serve_connection(io, service)
+        .await
+    {
+        log::warn!("Control socket connection error: {}", e);
+    }
+}
+
+/// Only accept connections from processes running as us: any other local
+/// uid could otherwise dial the socket and drive `handle_window_command`.
+#[cfg(unix)]
+fn unix_peer_is_self(stream: &UnixStream) -> bool {
+    match stream.peer_cred() {
+        Ok(cred) => cred.uid() == unsafe { libc::geteuid() },
+        Err(e) => {
+            log::warn!("Failed to read control socket peer credentials: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn accept_loop(socket_path: &PathBuf, router: Router) -> Result<(), Box<dyn std::error::Error>> {
     if socket_path.exists() {
-        fs::remove_file(&socket_path)?;
+        fs::remove_file(socket_path)?;
     }
 
-    let listener = UnixListener::bind(&socket_path)?;
+    let listener = UnixListener::bind(socket_path)?;
 
-    let state = AppState {
-        app_handle: Arc::new(Mutex::new(Some(app_handle))),
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _addr) = accepted?;
+                if !unix_peer_is_self(&stream) {
+                    log::warn!("Rejected control socket connection from untrusted peer");
+                    continue;
+                }
+                tokio::spawn(serve_connection(stream, router.clone()));
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    let _ = fs::remove_file(socket_path);
+
+    Ok(())
+}
+
+/// Only accept connections from the bundled FastAPI sidecar: compare the
+/// connecting process's PID against the PID of the sidecar we actually
+/// spawned, rather than trusting anything about its claimed identity.
+#[cfg(windows)]
+fn windows_peer_is_sidecar(pipe: &NamedPipeServer, app_handle: &AppHandle) -> bool {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Pipes::GetNamedPipeClientProcessId;
+
+    let Some(expected_pid) = crate::sidecar_pid(app_handle) else {
+        log::warn!("No sidecar process on record; rejecting control pipe connection");
+        return false;
     };
 
-    let app = create_router(state);
+    let mut client_pid: u32 = 0;
+    let ok = unsafe { GetNamedPipeClientProcessId(pipe.as_raw_handle() as _, &mut client_pid) != 0 };
+
+    ok && client_pid == expected_pid
+}
+
+#[cfg(windows)]
+async fn accept_loop(
+    pipe_name: &PathBuf,
+    router: Router,
+    app_handle: &AppHandle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pipe_name = pipe_name.to_string_lossy().into_owned();
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&pipe_name)?;
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    loop {
+        tokio::select! {
+            connected = server.connect() => {
+                connected?;
 
-    let _ = fs::remove_file(&socket_path);
+                // Hand off the connected instance and immediately stand up
+                // the next one so a new client always has a pipe to dial.
+                let connection = server;
+                server = ServerOptions::new().create(&pipe_name)?;
+
+                if !windows_peer_is_sidecar(&connection, app_handle) {
+                    log::warn!("Rejected control pipe connection from untrusted peer");
+                    continue;
+                }
+
+                tokio::spawn(serve_connection(connection, router.clone()));
+            }
+            _ = &mut shutdown => break,
+        }
+    }
 
     Ok(())
 }
 
+pub async fn run_socket_server(app_handle: AppHandle, token: String) -> Result<(), Box<dyn std::error::Error>> {
+    let socket_path = get_socket_path();
+
+    let (window_events, _rx) = broadcast::channel(64);
+
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let window_events = window_events.clone();
+        window.on_window_event(move |event| {
+            if let Some(message) = describe_window_event(event) {
+                // Err just means nobody's subscribed to /events right now.
+                let _ = window_events.send(message);
+            }
+        });
+    } else {
+        log::warn!("Main window not available at socket server startup; /events won't see window events");
+    }
+
+    #[cfg(windows)]
+    let peer_handle = app_handle.clone();
+
+    let state = AppState {
+        app_handle: Arc::new(Mutex::new(Some(app_handle))),
+        token: Arc::new(token),
+        window_events,
+    };
+
+    let router = create_router(state);
+
+    #[cfg(unix)]
+    return accept_loop(&socket_path, router).await;
+    #[cfg(windows)]
+    return accept_loop(&socket_path, router, &peer_handle).await;
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()